@@ -0,0 +1,96 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Types mirroring the subset of `cargo metadata`'s manifest-dependency
+//! shape that `Index` needs. This module defines only `DepKind` and
+//! `ManifestDep`; it does not define `Manifest`, `Metadata`, `Node`,
+//! `NodeDep`, `NodeDepKind`, `PkgId`, `TargetReq`, `ManifestTarget`, or
+//! `Source`, although `crate::index` and `crate::lockfile` import them from
+//! `crate::cargo`.
+
+use serde::Deserialize;
+use serde::Deserializer;
+
+use crate::index::ArtifactKind;
+
+/// The kind of build step a dependency applies to, matching the `kind` key
+/// `cargo metadata` reports for each `Package.dependencies` entry: absent
+/// (normal), `"dev"`, or `"build"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DepKind {
+    Normal,
+    Dev,
+    Build,
+}
+
+impl Default for DepKind {
+    fn default() -> Self {
+        DepKind::Normal
+    }
+}
+
+impl<'de> Deserialize<'de> for DepKind {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let kind: Option<String> = Option::deserialize(deserializer)?;
+        match kind.as_deref() {
+            None | Some("normal") => Ok(DepKind::Normal),
+            Some("dev") => Ok(DepKind::Dev),
+            Some("build") => Ok(DepKind::Build),
+            Some(other) => Err(serde::de::Error::custom(format!(
+                "unrecognized dependency kind: {}",
+                other
+            ))),
+        }
+    }
+}
+
+/// A single dependency entry as it appears in a package's manifest, i.e. one
+/// element of `cargo metadata`'s `Package.dependencies` array.
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq, Hash)]
+pub struct ManifestDep {
+    pub name: String,
+    pub rename: Option<String>,
+    #[serde(default)]
+    pub kind: DepKind,
+    /// The raw `cfg(...)` platform predicate this dependency is gated
+    /// behind, if any, in the form `PlatformPredicate::parse` expects.
+    pub target: Option<String>,
+    /// The build artifact kind(s) requested by Cargo's unstable `dep = {
+    /// artifact = "bin" }` / `{ artifact = ["bin", "cdylib"] }` syntax.
+    /// Empty for an ordinary (non-artifact) dependency.
+    #[serde(default, deserialize_with = "one_or_many_artifact_kinds")]
+    pub artifact: Vec<ArtifactKind>,
+    /// The optional `target = "..."` cross-compile selector from an
+    /// artifact dependency, e.g. a build-time tool that must itself be
+    /// built for the host even when the rest of the graph is being
+    /// cross-compiled.
+    #[serde(default)]
+    pub artifact_target: Option<String>,
+}
+
+/// `artifact` may be written as a single string (`artifact = "bin"`) or an
+/// array (`artifact = ["bin", "cdylib"]`); accept either.
+fn one_or_many_artifact_kinds<'de, D>(deserializer: D) -> Result<Vec<ArtifactKind>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(ArtifactKind),
+        Many(Vec<ArtifactKind>),
+    }
+
+    match Option::<OneOrMany>::deserialize(deserializer)? {
+        None => Ok(Vec::new()),
+        Some(OneOrMany::One(kind)) => Ok(vec![kind]),
+        Some(OneOrMany::Many(kinds)) => Ok(kinds),
+    }
+}
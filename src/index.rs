@@ -7,13 +7,17 @@
 
 //! Index for Cargo metadata, and various useful traversals.
 
+use std::collections::btree_map;
 use std::collections::BTreeMap;
 use std::collections::BTreeSet;
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::collections::VecDeque;
 use std::error;
 use std::fmt;
 
+use anyhow::anyhow;
+use anyhow::bail;
 use anyhow::Result;
 use serde::Deserialize;
 
@@ -45,12 +49,104 @@ pub struct Index<'meta> {
     /// - root_pkg, if it is being made public (aka "real", and not just a pseudo package)
     /// - first-order dependencies of root_pkg, including artifact dependencies
     public_targets: BTreeMap<(&'meta PkgId, TargetReq<'meta>), Option<&'meta str>>,
+    /// Features enabled for each package, tagged with the platform they're
+    /// active under. Populated from a single unconditional resolve by
+    /// `Index::new` (every feature maps to `platform: None`), or from one
+    /// resolve per target platform by `Index::new_for_platforms` (features
+    /// only active on some platforms get an entry per platform instead).
+    resolved_features: HashMap<&'meta PkgId, Vec<ResolvedFeature<'meta>>>,
+    /// Effective privilege/visibility group rank for each package, as a
+    /// position into a `PrivilegeGroups` order. Empty unless
+    /// `Index::with_effective_groups` has been called. See
+    /// `Index::effective_group`.
+    effective_groups: HashMap<&'meta PkgId, usize>,
+}
+
+/// A feature enabled for a package, and the platform (if any) under which
+/// it's active. `platform: None` means the feature is enabled regardless of
+/// target platform.
+#[derive(Debug, Clone)]
+pub struct ResolvedFeature<'meta> {
+    pub platform: Option<PlatformExpr>,
+    pub feature: &'meta str,
 }
 
 /// Extra per-package metadata to be kept in sync with the package list
 #[derive(Debug, Deserialize)]
 pub struct ExtraMetadata {
     pub oncall: String, // oncall shortname for use as maintainer
+    /// Security/visibility group, e.g. "sandbox", "test", "safe". Must name
+    /// a group configured in `PrivilegeGroups`. See `Index::effective_group`.
+    #[serde(default)]
+    pub group: Option<String>,
+}
+
+/// A total order of privilege/visibility group names, least to most
+/// privileged, as configured for the workspace (e.g. `["safe", "test",
+/// "sandbox"]`). Used to compute each package's *effective* group: the most
+/// privileged group of itself or anything that depends on it.
+#[derive(Debug, Clone)]
+pub struct PrivilegeGroups {
+    order: Vec<String>,
+}
+
+impl PrivilegeGroups {
+    pub fn new(order: Vec<String>) -> Self {
+        PrivilegeGroups { order }
+    }
+
+    fn rank(&self, group: &str) -> Option<usize> {
+        self.order.iter().position(|g| g == group)
+    }
+
+    fn name(&self, rank: usize) -> &str {
+        &self.order[rank]
+    }
+}
+
+/// Cumulative errors where a package's declared privilege group is weaker
+/// than the group it inherits from something that depends on it.
+#[derive(Debug, Clone)]
+struct PrivilegeError {
+    violations: BTreeMap<String, (String, String)>,
+}
+
+impl PrivilegeError {
+    fn new() -> Self {
+        PrivilegeError {
+            violations: BTreeMap::new(),
+        }
+    }
+
+    fn all_ok(&self) -> bool {
+        self.violations.is_empty()
+    }
+
+    fn add(&mut self, pkg: impl ToString, declared: impl ToString, inherited: impl ToString) {
+        self.violations.insert(
+            pkg.to_string(),
+            (declared.to_string(), inherited.to_string()),
+        );
+    }
+}
+
+impl error::Error for PrivilegeError {}
+
+impl fmt::Display for PrivilegeError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmt,
+            "Package(s) declared with a weaker privilege group than they inherit:"
+        )?;
+        for (pkg, (declared, inherited)) in &self.violations {
+            write!(
+                fmt,
+                " {} (declared `{}`, but inherits `{}` from a dependent)",
+                pkg, declared, inherited,
+            )?;
+        }
+        Ok(())
+    }
 }
 
 // Cumulative errors in package metadata
@@ -91,19 +187,233 @@ impl fmt::Display for PackageMetaError {
     }
 }
 
+/// Cumulative errors where the same underlying crate is depended on under
+/// two different, irreconcilable renames.
+#[derive(Debug, Clone)]
+struct RenameConflictError {
+    conflicts: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl RenameConflictError {
+    fn new() -> Self {
+        RenameConflictError {
+            conflicts: BTreeMap::new(),
+        }
+    }
+
+    fn all_ok(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+
+    fn add_conflict(&mut self, pkg: &str, a: &str, b: &str) {
+        let names = self.conflicts.entry(pkg.to_owned()).or_default();
+        names.insert(a.to_owned());
+        names.insert(b.to_owned());
+    }
+}
+
+impl error::Error for RenameConflictError {}
+
+impl fmt::Display for RenameConflictError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "Conflicting renames for package(s):")?;
+        for (pkg, names) in &self.conflicts {
+            write!(
+                fmt,
+                " {} (renamed as {})",
+                pkg,
+                names.iter().cloned().collect::<Vec<_>>().join(", "),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Best-effort check for whether two raw `cfg(...)` platform predicate
+/// strings (as written in a manifest dep's `target = "..."` key, before
+/// `PlatformPredicate::parse`) can never both be true at once.
+///
+/// This only recognizes a handful of syntactically obvious cases: the
+/// `unix`/`windows` family split, and differing `target_os = "..."` or
+/// `target_family = "..."` literals, each considering *every* occurrence of
+/// the relevant key/family member (so `any(target_os = "windows",
+/// target_os = "linux")` is correctly treated as overlapping a bare
+/// `target_os = "linux"`, not just compared against the first occurrence).
+/// Anything it doesn't recognize is conservatively treated as
+/// possibly-overlapping (not disjoint), so this never waves through a
+/// genuine conflict just because it failed to understand the predicates
+/// involved - including `not(...)`, which we don't attempt to reason about
+/// at all (a substring match inside a negation, e.g. "unix" inside
+/// `not(unix)`, means the opposite of what it would unnegated).
+fn platform_predicates_disjoint(a: &str, b: &str) -> bool {
+    if a.contains("not(") || b.contains("not(") {
+        return false;
+    }
+
+    const FAMILIES: &[&[&str]] = &[&["unix", "windows"]];
+    for family in FAMILIES {
+        let a_members: HashSet<&str> = family.iter().copied().filter(|f| a.contains(f)).collect();
+        let b_members: HashSet<&str> = family.iter().copied().filter(|f| b.contains(f)).collect();
+        if !a_members.is_empty() && !b_members.is_empty() && a_members.is_disjoint(&b_members) {
+            return true;
+        }
+    }
+
+    for key in ["target_os", "target_family"] {
+        let a_vals: HashSet<&str> = extract_cfg_values(a, key).into_iter().collect();
+        let b_vals: HashSet<&str> = extract_cfg_values(b, key).into_iter().collect();
+        if !a_vals.is_empty() && !b_vals.is_empty() && a_vals.is_disjoint(&b_vals) {
+            return true;
+        }
+    }
+
+    false
+}
+
+/// Extract every string literal value of `key = "..."` occurring in a raw
+/// `cfg(...)` predicate, e.g. `extract_cfg_values(r#"any(target_os =
+/// "windows", target_os = "linux")"#, "target_os")` returns `["windows",
+/// "linux"]`.
+fn extract_cfg_values<'a>(predicate: &'a str, key: &str) -> Vec<&'a str> {
+    let mut values = Vec::new();
+    let mut rest = predicate;
+    while let Some(idx) = rest.find(key) {
+        let after_key = &rest[idx + key.len()..];
+        if let Some(value) = after_key
+            .trim_start()
+            .strip_prefix('=')
+            .map(|s| s.trim_start())
+            .and_then(|s| s.strip_prefix('"'))
+            .and_then(|s| s.find('"').map(|end| &s[..end]))
+        {
+            values.push(value);
+        }
+        // Always advance past this occurrence of `key` so a match that
+        // didn't parse as `key = "..."` can't spin the loop forever.
+        rest = after_key;
+    }
+    values
+}
+
 #[derive(Debug, Clone)]
 pub struct ResolvedDep<'meta> {
     pub package: &'meta Manifest,
     pub platform: Option<PlatformExpr>,
     pub rename: &'meta str,
     pub dep_kind: &'meta NodeDepKind,
+    /// Set when this is an artifact dependency (Cargo's unstable `dep =
+    /// { artifact = "bin" }` syntax) rather than an ordinary lib dependency.
+    pub artifact: Option<ArtifactDep<'meta>>,
+}
+
+/// The kind of build artifact an artifact dependency (`artifact = "bin"` /
+/// `"cdylib"` / `"staticlib"`) requests from its dependency package. See
+/// <https://doc.rust-lang.org/cargo/reference/unstable.html#artifact-dependencies>.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArtifactKind {
+    Bin,
+    Cdylib,
+    Staticlib,
+}
+
+impl ArtifactKind {
+    fn matches(self, tgt: &ManifestTarget) -> bool {
+        match self {
+            ArtifactKind::Bin => tgt.kind_bin(),
+            ArtifactKind::Cdylib => tgt.kind_cdylib(),
+            ArtifactKind::Staticlib => tgt.kind_staticlib(),
+        }
+    }
+
+    /// The prefix of the `CARGO_*_FILE_<name>` environment variable Cargo
+    /// sets for consumers of an artifact dependency of this kind.
+    pub fn env_var_prefix(self) -> &'static str {
+        match self {
+            ArtifactKind::Bin => "CARGO_BIN_FILE",
+            ArtifactKind::Cdylib => "CARGO_CDYLIB_FILE",
+            ArtifactKind::Staticlib => "CARGO_STATICLIB_FILE",
+        }
+    }
+}
+
+/// The targets of a dependency package that satisfy one requested artifact
+/// kind.
+#[derive(Debug, Clone)]
+pub struct ArtifactKindTargets<'meta> {
+    pub kind: ArtifactKind,
+    pub targets: Vec<&'meta ManifestTarget>,
+}
+
+/// An artifact dependency's resolution against its dependency package: the
+/// target(s) that satisfy each requested artifact kind, plus the optional
+/// `target = "..."` cross-compile selector the manifest dep requested
+/// (e.g. a build-time tool that must itself be built for the host even
+/// when the rest of the graph is being cross-compiled).
+#[derive(Debug, Clone)]
+pub struct ArtifactDep<'meta> {
+    pub kinds: Vec<ArtifactKindTargets<'meta>>,
+    pub target: Option<&'meta str>,
+}
+
+/// Fixed-point propagation of privilege/visibility groups down a dependency
+/// graph: `declared` gives each package's own declared group rank, and
+/// `deps(pkgid)` yields the packages it directly depends on. Returns
+/// `(effective, inherited)`, where `effective` is the running max group for
+/// each package (seeded with its own declared group), and `inherited` is the
+/// max contributed purely by things that depend on it - tracked separately
+/// so a package's own declaration can be told apart from what it would
+/// otherwise inherit.
+///
+/// Generic over the package-id type and kept free of any `Index` internals
+/// so the BFS/fixed-point logic itself can be tested directly, without
+/// constructing a full `Index`.
+fn propagate_privilege<K, I>(
+    declared: &HashMap<K, usize>,
+    deps: impl Fn(K) -> I,
+) -> (HashMap<K, usize>, HashMap<K, usize>)
+where
+    K: Eq + std::hash::Hash + Copy,
+    I: IntoIterator<Item = K>,
+{
+    let mut effective: HashMap<K, usize> = declared.clone();
+    let mut inherited: HashMap<K, usize> = HashMap::new();
+    let mut queue: VecDeque<K> = effective.keys().copied().collect();
+
+    while let Some(pkgid) = queue.pop_front() {
+        let current = effective[&pkgid];
+        for dep in deps(pkgid) {
+            let dep_inherited = inherited.entry(dep).or_insert(0);
+            if *dep_inherited < current {
+                *dep_inherited = current;
+            }
+            match effective.entry(dep) {
+                std::collections::hash_map::Entry::Vacant(entry) => {
+                    entry.insert(current);
+                    queue.push_back(dep);
+                }
+                std::collections::hash_map::Entry::Occupied(mut entry) => {
+                    if *entry.get() < current {
+                        entry.insert(current);
+                        queue.push_back(dep);
+                    }
+                }
+            }
+        }
+    }
+
+    (effective, inherited)
 }
 
 impl<'meta> Index<'meta> {
     /// Construct an index for a set of Cargo metadata to allow convenient and efficient
     /// queries. The metadata represents a top level package and all its transitive
     /// dependencies.
-    pub fn new(root_is_real: bool, metadata: &'meta Metadata) -> Index<'meta> {
+    ///
+    /// Fails if the root package depends on the same underlying crate under
+    /// two different renames that can't be told apart at the Buck level
+    /// (see `RenameConflictError`).
+    pub fn new(root_is_real: bool, metadata: &'meta Metadata) -> Result<Index<'meta>> {
         let pkgid_to_pkg: HashMap<_, _> = metadata.packages.iter().map(|m| (&m.id, m)).collect();
 
         let root_pkg: &Manifest = pkgid_to_pkg
@@ -115,12 +425,31 @@ impl<'meta> Index<'meta> {
             top_levels.insert(&root_pkg.id);
         }
 
+        let resolved_features = metadata
+            .resolve
+            .nodes
+            .iter()
+            .map(|node| {
+                let features = node
+                    .features
+                    .iter()
+                    .map(|feature| ResolvedFeature {
+                        platform: None,
+                        feature: feature.as_str(),
+                    })
+                    .collect();
+                (&node.id, features)
+            })
+            .collect();
+
         let mut tmp = Index {
             pkgid_to_pkg,
             pkgid_to_node: metadata.resolve.nodes.iter().map(|n| (&n.id, n)).collect(),
             root_pkg,
             public_packages: BTreeSet::new(),
             public_targets: BTreeMap::new(),
+            resolved_features,
+            effective_groups: HashMap::new(),
         };
 
         // Keep an index of renamed crates, mapping from _ normalized name to actual name
@@ -133,31 +462,151 @@ impl<'meta> Index<'meta> {
             })
             .collect();
 
+        // Map from a root-level dependency's resolved extern name (its
+        // rename if it has one, otherwise its own name, _ normalized like
+        // `dep_renamed`) to the raw platform predicate it's gated behind, if
+        // any. Used below to tell a legitimate same-crate-different-predicate
+        // split apart from a genuine rename conflict.
+        let name_to_platform: HashMap<String, Option<&'meta str>> = root_pkg
+            .dependencies
+            .iter()
+            .map(|dep| {
+                let extern_name = dep.rename.as_deref().unwrap_or(dep.name.as_str());
+                (extern_name.replace('-', "_"), dep.target.as_deref())
+            })
+            .collect();
+
         // Compute public set, with pkgid mapped to rename if it has one. Public set is
         // anything in top_levels, or first-order dependencies of root_pkg.
-        let public_targets = tmp
+        //
+        // A given (pkgid, target_req) can legitimately show up more than
+        // once here, e.g. when root_pkg depends on the same underlying
+        // crate twice under different local names gated by disjoint
+        // `cfg(...)` predicates (`bar = { package = "foo" }` on unix,
+        // `baz = { package = "foo" }` on windows). Tell that case apart
+        // from a genuine conflict (the same crate renamed two different,
+        // incompatible ways) by checking whether the two renames' platform
+        // predicates are provably disjoint; only error when they're not
+        // (including when either side is unconditional, since an
+        // unconditional dependency can't be disjoint from anything).
+        let mut public_targets = BTreeMap::new();
+        let mut rename_platform: HashMap<(&'meta PkgId, TargetReq<'meta>), Option<&'meta str>> =
+            HashMap::new();
+        let mut rename_conflicts = RenameConflictError::new();
+        for (key, opt_rename, platform) in tmp
             .resolved_deps(tmp.root_pkg)
-            .flat_map(|(rename, dep_kind, pkg)| {
+            .map(|(rename, dep_kind, pkg)| {
                 let target_req = dep_kind.target_req();
                 let opt_rename = dep_renamed.get(rename).cloned();
-                vec![((&pkg.id, target_req), opt_rename)]
+                let platform = name_to_platform.get(rename).copied().flatten();
+                ((&pkg.id, target_req), opt_rename, platform)
             })
             .chain(top_levels.iter().flat_map(|pkgid| {
                 [
-                    ((*pkgid, TargetReq::Lib), None),
-                    ((*pkgid, TargetReq::EveryBin), None),
+                    ((*pkgid, TargetReq::Lib), None, None),
+                    ((*pkgid, TargetReq::EveryBin), None, None),
                 ]
             }))
-            .collect::<BTreeMap<_, _>>();
+        {
+            match public_targets.entry(key) {
+                btree_map::Entry::Vacant(entry) => {
+                    entry.insert(opt_rename);
+                    rename_platform.insert(key, platform);
+                }
+                btree_map::Entry::Occupied(mut entry) => match (*entry.get(), opt_rename) {
+                    (Some(existing), Some(new)) if existing != new => {
+                        let existing_platform = rename_platform.get(&key).copied().flatten();
+                        let disjoint = match (existing_platform, platform) {
+                            (Some(a), Some(b)) => platform_predicates_disjoint(a, b),
+                            _ => false,
+                        };
+                        if !disjoint {
+                            rename_conflicts.add_conflict(&format!("{:?}", key.0), existing, new);
+                        }
+                    }
+                    (None, Some(_)) => {
+                        entry.insert(opt_rename);
+                        rename_platform.insert(key, platform);
+                    }
+                    _ => {}
+                },
+            }
+        }
+
+        if !rename_conflicts.all_ok() {
+            return Err(rename_conflicts.into());
+        }
 
         for (pkg, _kind) in public_targets.keys() {
             tmp.public_packages.insert(pkg);
         }
 
-        Index {
+        Ok(Index {
             public_targets,
             ..tmp
+        })
+    }
+
+    /// Like `Index::new`, but additionally takes the `cargo metadata` resolve
+    /// obtained for each configured target platform (e.g. by invoking
+    /// metadata once per triple with `--filter-platform`) and merges them so
+    /// that `resolved_features` reports which features are only active
+    /// under some platforms, rather than flattening everything into one
+    /// unconditional set.
+    ///
+    /// A feature present in every per-platform resolve is reported as
+    /// unconditional (`platform: None`); a feature present on only some
+    /// platforms gets one `resolved_features` entry per platform it's
+    /// active under, each tagged with that platform's `PlatformExpr`, so
+    /// callers can group them into a `select({...})`.
+    pub fn new_for_platforms(
+        root_is_real: bool,
+        metadata: &'meta Metadata,
+        platform_resolves: &'meta [(PlatformExpr, Metadata)],
+    ) -> Result<Index<'meta>> {
+        let mut index = Self::new(root_is_real, metadata)?;
+        if platform_resolves.is_empty() {
+            return Ok(index);
+        }
+
+        // For each package, and each feature enabled for it on any
+        // platform, collect the set of platforms that enable it.
+        let mut by_pkg: HashMap<&'meta PkgId, HashMap<&'meta str, Vec<&'meta PlatformExpr>>> =
+            HashMap::new();
+        for (platform, platform_metadata) in platform_resolves {
+            for node in &platform_metadata.resolve.nodes {
+                let features = by_pkg.entry(&node.id).or_default();
+                for feature in &node.features {
+                    features.entry(feature.as_str()).or_default().push(platform);
+                }
+            }
+        }
+
+        let total_platforms = platform_resolves.len();
+        let mut resolved_features = HashMap::new();
+        for (pkgid, features) in by_pkg {
+            let mut list = Vec::new();
+            for (feature, platforms) in features {
+                if platforms.len() == total_platforms {
+                    // Active under every configured platform: unconditional.
+                    list.push(ResolvedFeature {
+                        platform: None,
+                        feature,
+                    });
+                } else {
+                    for platform in platforms {
+                        list.push(ResolvedFeature {
+                            platform: Some(platform.clone()),
+                            feature,
+                        });
+                    }
+                }
+            }
+            resolved_features.insert(pkgid, list);
         }
+
+        index.resolved_features = resolved_features;
+        Ok(index)
     }
 
     /// Test if a package is the root package
@@ -232,14 +681,86 @@ impl<'meta> Index<'meta> {
         }
     }
 
-    /// Return the set of features resolved for a particular package
-    pub fn resolved_features(&self, pkg: &Manifest) -> impl Iterator<Item = &'meta str> {
-        self.pkgid_to_node
-            .get(&pkg.id)
-            .unwrap()
-            .features
-            .iter()
-            .map(String::as_str)
+    /// Compute the effective privilege/visibility group for every package
+    /// reachable from `root_pkg`, given the workspace's total order of
+    /// groups and each package's declared group (as validated by
+    /// `get_extra_meta`).
+    ///
+    /// Privilege propagates downward through the dependency graph: walking
+    /// `NodeDep` edges from each package with a declared group, every
+    /// dependency reachable from it is assigned at least that group, taking
+    /// the max over all such reverse-dependents. A package whose own
+    /// declared group is weaker than what it would inherit this way is
+    /// reported as an error, the same way `get_extra_meta` reports metadata
+    /// for packages that aren't in the package list.
+    pub fn with_effective_groups(
+        mut self,
+        groups: &PrivilegeGroups,
+        declared: &HashMap<&'meta str, ExtraMetadata>,
+    ) -> Result<Self> {
+        let mut declared_rank: HashMap<&'meta PkgId, usize> = HashMap::new();
+        for pkg in self.pkgid_to_pkg.values() {
+            if let Some(meta) = declared.get(pkg.name.as_str()) {
+                if let Some(group) = &meta.group {
+                    let rank = groups.rank(group).ok_or_else(|| {
+                        anyhow!("Unknown privilege group `{}` for `{}`", group, pkg)
+                    })?;
+                    declared_rank.insert(&pkg.id, rank);
+                }
+            }
+        }
+
+        let (effective, inherited) = propagate_privilege(&declared_rank, |pkgid| {
+            self.pkgid_to_node
+                .get(pkgid)
+                .into_iter()
+                .flat_map(|node| node.deps.iter().map(|dep| &dep.pkg))
+        });
+
+        let mut violations = PrivilegeError::new();
+        for (pkgid, inherited_rank) in &inherited {
+            if let Some(&declared_rank) = declared_rank.get(pkgid) {
+                if declared_rank < *inherited_rank {
+                    let pkg = self.pkgid_to_pkg[pkgid];
+                    violations.add(
+                        pkg,
+                        groups.name(declared_rank),
+                        groups.name(*inherited_rank),
+                    );
+                }
+            }
+        }
+        if !violations.all_ok() {
+            return Err(violations.into());
+        }
+
+        self.effective_groups = effective;
+        Ok(self)
+    }
+
+    /// Return the effective privilege/visibility group for a package, as
+    /// computed by `Index::with_effective_groups`. `None` if that hasn't
+    /// been called, or if neither the package nor anything depending on it
+    /// declared a group.
+    pub fn effective_group<'a>(
+        &self,
+        pkg: &Manifest,
+        groups: &'a PrivilegeGroups,
+    ) -> Option<&'a str> {
+        let rank = *self.effective_groups.get(&pkg.id)?;
+        Some(groups.name(rank))
+    }
+
+    /// Return the set of features resolved for a particular package, each
+    /// tagged with the platform (if any) under which it's active. Features
+    /// that are active regardless of platform (the common case, and always
+    /// the case unless the index was built with `Index::new_for_platforms`)
+    /// have `platform: None`.
+    pub fn resolved_features(
+        &self,
+        pkg: &Manifest,
+    ) -> impl Iterator<Item = &ResolvedFeature<'meta>> + '_ {
+        self.resolved_features.get(&pkg.id).into_iter().flatten()
     }
 
     /// Return the resolved dependencies for a package
@@ -288,12 +809,46 @@ impl<'meta> Index<'meta> {
         })
     }
 
+    /// Resolve an artifact dependency's requested kinds against the actual
+    /// targets of its dependency package. Errors if any requested kind has
+    /// no matching target: a misconfigured `artifact = ["cdylib"]` against
+    /// a lib-only crate must fail the run rather than silently produce a
+    /// Buck rule missing the artifact (and its `CARGO_CDYLIB_FILE_*` env
+    /// var) that consumers expect.
+    fn resolve_artifact_dep(
+        &self,
+        dep_pkg: &'meta Manifest,
+        requested_kinds: &[ArtifactKind],
+        target: Option<&'meta str>,
+    ) -> Result<ArtifactDep<'meta>> {
+        let kinds: Vec<ArtifactKindTargets<'meta>> = requested_kinds
+            .iter()
+            .map(|&kind| {
+                let targets: Vec<&'meta ManifestTarget> = dep_pkg
+                    .targets
+                    .iter()
+                    .filter(|tgt| kind.matches(tgt))
+                    .collect();
+                if targets.is_empty() {
+                    bail!(
+                        "Artifact dependency on {} requests `{:?}` but it has no matching target",
+                        dep_pkg,
+                        kind,
+                    );
+                }
+                Ok(ArtifactKindTargets { kind, targets })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(ArtifactDep { kinds, target })
+    }
+
     /// Return resolved dependencies for a target
     pub fn resolved_deps_for_target(
         &self,
         pkg: &'meta Manifest,
         tgt: &'meta ManifestTarget,
-    ) -> impl Iterator<Item = ResolvedDep<'meta>> + '_ {
+    ) -> impl Iterator<Item = Result<ResolvedDep<'meta>>> + '_ {
         // Unresolved dependency names
         let mut deps = HashMap::new();
 
@@ -332,7 +887,31 @@ impl<'meta> Index<'meta> {
                     }
                 }
 
-                Some(ResolvedDep {
+                // Artifact dependencies (`dep = { artifact = "bin" }`) request
+                // a specific build output of `dep`, rather than linking its
+                // lib target as usual. All the manifest deps that coalesced
+                // into this one resolved dep should agree on the artifact
+                // request; if more than one specifies it, the last one wins,
+                // same as the "probably over-engineered" platform union
+                // above assumes they won't actually conflict in practice.
+                let mut artifact_kinds: &[ArtifactKind] = &[];
+                let mut artifact_cross_target = None;
+                for mdep in mdeps {
+                    if !mdep.artifact.is_empty() {
+                        artifact_kinds = &mdep.artifact;
+                        artifact_cross_target = mdep.artifact_target.as_deref();
+                    }
+                }
+                let artifact = if artifact_kinds.is_empty() {
+                    None
+                } else {
+                    match self.resolve_artifact_dep(dep, artifact_kinds, artifact_cross_target) {
+                        Ok(artifact) => Some(artifact),
+                        Err(err) => return Some(Err(err)),
+                    }
+                };
+
+                Some(Ok(ResolvedDep {
                     package: dep,
                     platform: match &*platforms {
                         [] => None,
@@ -341,7 +920,122 @@ impl<'meta> Index<'meta> {
                     },
                     rename,
                     dep_kind,
-                })
+                    artifact,
+                }))
             })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn propagate_privilege_direct_inheritance() {
+        // a -> b, a declared "sandbox" (rank 2). b should inherit rank 2.
+        let mut declared = HashMap::new();
+        declared.insert("a", 2);
+        let deps = |pkgid| match pkgid {
+            "a" => vec!["b"],
+            _ => vec![],
+        };
+
+        let (effective, inherited) = propagate_privilege(&declared, deps);
+
+        assert_eq!(effective.get("b"), Some(&2));
+        assert_eq!(inherited.get("b"), Some(&2));
+    }
+
+    #[test]
+    fn propagate_privilege_transitive_inheritance() {
+        // a -> b -> c, a declared "sandbox" (rank 2). c does not directly
+        // depend on a, but should still inherit rank 2 transitively through b.
+        let mut declared = HashMap::new();
+        declared.insert("a", 2);
+        let deps = |pkgid| match pkgid {
+            "a" => vec!["b"],
+            "b" => vec!["c"],
+            _ => vec![],
+        };
+
+        let (effective, inherited) = propagate_privilege(&declared, deps);
+
+        assert_eq!(effective.get("c"), Some(&2));
+        assert_eq!(inherited.get("c"), Some(&2));
+    }
+
+    #[test]
+    fn propagate_privilege_takes_max_over_multiple_dependents() {
+        // a (rank 1) -> c, b (rank 2) -> c. c should inherit the stronger
+        // rank 2 from b, not be capped at a's weaker rank 1.
+        let mut declared = HashMap::new();
+        declared.insert("a", 1);
+        declared.insert("b", 2);
+        let deps = |pkgid| match pkgid {
+            "a" => vec!["c"],
+            "b" => vec!["c"],
+            _ => vec![],
+        };
+
+        let (_, inherited) = propagate_privilege(&declared, deps);
+
+        assert_eq!(inherited.get("c"), Some(&2));
+    }
+
+    #[test]
+    fn unix_and_windows_predicates_are_disjoint() {
+        assert!(platform_predicates_disjoint(
+            r#"cfg(unix)"#,
+            r#"cfg(windows)"#
+        ));
+    }
+
+    #[test]
+    fn differing_target_os_literals_are_disjoint() {
+        assert!(platform_predicates_disjoint(
+            r#"cfg(target_os = "windows")"#,
+            r#"cfg(target_os = "macos")"#,
+        ));
+    }
+
+    #[test]
+    fn identical_predicates_are_not_disjoint() {
+        assert!(!platform_predicates_disjoint(
+            r#"cfg(target_os = "linux")"#,
+            r#"cfg(target_os = "linux")"#,
+        ));
+    }
+
+    #[test]
+    fn negated_unix_and_windows_are_not_disjoint() {
+        // not(unix) and windows both hold on Windows, so despite the
+        // substring "unix" appearing in the first predicate, these overlap
+        // rather than being disjoint.
+        assert!(!platform_predicates_disjoint(
+            r#"cfg(not(unix))"#,
+            r#"cfg(windows)"#,
+        ));
+    }
+
+    #[test]
+    fn multi_clause_any_overlapping_with_single_clause_is_not_disjoint() {
+        // any(target_os = "windows", target_os = "linux") and a bare
+        // target_os = "linux" both hold on Linux, so they overlap even
+        // though the first `target_os` occurrence in the left-hand side
+        // ("windows") differs from the right-hand side.
+        assert!(!platform_predicates_disjoint(
+            r#"cfg(any(target_os = "windows", target_os = "linux"))"#,
+            r#"cfg(target_os = "linux")"#,
+        ));
+    }
+
+    #[test]
+    fn unrecognized_predicates_are_conservatively_not_disjoint() {
+        // Neither predicate matches a pattern we understand, so we can't
+        // prove they're disjoint - and must not claim that they are.
+        assert!(!platform_predicates_disjoint(
+            r#"cfg(feature = "a")"#,
+            r#"cfg(feature = "b")"#,
+        ));
+    }
+}
@@ -5,12 +5,17 @@
  * LICENSE file in the root directory of this source tree.
  */
 
+use std::fmt;
 use std::fs;
+use std::path::Path;
 
+use anyhow::bail;
 use anyhow::Context;
 use anyhow::Result;
 use serde::Deserialize;
 use serde::Deserializer;
+use sha2::Digest;
+use sha2::Sha256;
 
 use crate::cargo::Manifest;
 use crate::cargo::Source;
@@ -18,7 +23,10 @@ use crate::Paths;
 
 #[derive(Deserialize, Debug)]
 pub struct Lockfile {
-    pub version: Hopefully3,
+    // The `version` key was only added starting with format v3; v1 and v2
+    // lockfiles have no `version` field in the TOML at all, so this has to
+    // be optional rather than required.
+    pub version: Option<LockfileVersion>,
     #[serde(rename = "package")]
     pub packages: Vec<LockfilePackage>,
 }
@@ -27,9 +35,49 @@ impl Lockfile {
     pub fn load(paths: &Paths) -> Result<Self> {
         let cargo_lock_content = fs::read(&paths.lockfile_path)
             .with_context(|| format!("Failed to load {}", paths.lockfile_path.display()))?;
+        Self::parse(&cargo_lock_content)
+            .with_context(|| format!("Failed to parse {}", paths.lockfile_path.display()))
+    }
+
+    /// Parse the raw contents of a `Cargo.lock`, handling all four lockfile
+    /// format versions. Split out from `load` so the version-detection and
+    /// checksum-backfill logic can be exercised directly against fixture
+    /// strings in tests, without needing a `Paths` pointing at a real file.
+    fn parse(cargo_lock_content: &[u8]) -> Result<Self> {
+        let mut lockfile: Lockfile = toml::from_slice(cargo_lock_content)?;
+
+        let raw: RawLockfileMetadata = toml::from_slice(cargo_lock_content)?;
+
+        // No `version` key means this is a v1 or v2 lockfile. Tell them
+        // apart the same way Cargo itself does: v2 is the version that
+        // started recording per-package checksums in the `[metadata]`
+        // table, so their presence means v2, their absence v1.
+        let version = lockfile.version.unwrap_or_else(|| {
+            if raw.metadata.keys().any(|key| key.starts_with("checksum ")) {
+                LockfileVersion::V2
+            } else {
+                LockfileVersion::V1
+            }
+        });
+        lockfile.version = Some(version);
 
-        let mut lockfile: Lockfile = toml::from_slice(&cargo_lock_content)
-            .with_context(|| format!("Failed to parse {}", paths.lockfile_path.display()))?;
+        // Versions 1 and 2 don't inline the checksum in each `[[package]]`
+        // table; instead it's recorded in a separate `[metadata]` table keyed
+        // by "checksum <name> <version> (<source>)". Backfill it so that
+        // callers can treat `LockfilePackage::checksum` uniformly regardless
+        // of the lockfile version that produced it.
+        if matches!(version, LockfileVersion::V1 | LockfileVersion::V2) {
+            for pkg in &mut lockfile.packages {
+                let source = match &pkg.source {
+                    Some(source) => source.to_string(),
+                    None => continue,
+                };
+                let key = format!("checksum {} {} ({})", pkg.name, pkg.version, source);
+                if let Some(checksum) = raw.metadata.get(&key) {
+                    pkg.checksum = Some(checksum.clone());
+                }
+            }
+        }
 
         lockfile.packages.sort_by(|a, b| {
             let a = (&a.name, &a.version, &a.source);
@@ -50,10 +98,60 @@ impl Lockfile {
             Err(_) => None,
         }
     }
+
+    /// Verify that every vendored package already present under
+    /// `paths.vendor_dir` matches the checksum recorded for it in
+    /// `Cargo.lock`. Intended to back a `--frozen`/`--locked`-style flag so
+    /// CI can guarantee the generated Buck targets reference exactly the
+    /// bytes pinned in the lockfile, rather than whatever happens to be
+    /// sitting in the vendor directory.
+    ///
+    /// Packages with no recorded checksum (e.g. path or git dependencies)
+    /// and packages that haven't been vendored yet are skipped; this is
+    /// purely a consistency check on what's already on disk.
+    pub fn verify(&self, paths: &Paths) -> Result<()> {
+        for pkg in &self.packages {
+            let Some(expected) = &pkg.checksum else {
+                continue;
+            };
+            let crate_dir = paths
+                .vendor_dir
+                .join(format!("{}-{}", pkg.name, pkg.version));
+            if !crate_dir.is_dir() {
+                continue;
+            }
+
+            pkg.verify_vendored(&crate_dir, expected).with_context(|| {
+                format!(
+                    "Failed to verify vendored copy of {} {}",
+                    pkg.name, pkg.version
+                )
+            })?;
+        }
+        Ok(())
+    }
+}
+
+/// The `version` field of a `Cargo.lock`, as documented at
+/// <https://doc.rust-lang.org/cargo/reference/resolver.html#lock-files>.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LockfileVersion {
+    V1,
+    V2,
+    V3,
+    V4,
 }
 
-#[derive(Debug)]
-pub struct Hopefully3;
+impl LockfileVersion {
+    fn as_usize(self) -> usize {
+        match self {
+            LockfileVersion::V1 => 1,
+            LockfileVersion::V2 => 2,
+            LockfileVersion::V3 => 3,
+            LockfileVersion::V4 => 4,
+        }
+    }
+}
 
 #[derive(Deserialize, Debug)]
 pub struct LockfilePackage {
@@ -63,15 +161,259 @@ pub struct LockfilePackage {
     pub checksum: Option<String>,
 }
 
-impl<'de> Deserialize<'de> for Hopefully3 {
+impl LockfilePackage {
+    /// Verify that the vendored copy of this package living at `crate_dir`
+    /// still matches `expected` (`self.checksum`, as recorded in
+    /// `Cargo.lock`) and hasn't been tampered with since vendoring.
+    ///
+    /// This is a two-part check, since neither half alone proves anything:
+    /// - `.cargo-checksum.json`'s `"package"` field is compared against
+    ///   `expected`. That field is *not* recomputed from file contents, so
+    ///   on its own it only catches a vendor tree pinned to the wrong
+    ///   package version/source, not local edits to the files themselves.
+    /// - Every file `.cargo-checksum.json` records in its `"files"` map is
+    ///   re-hashed and compared against the digest recorded there, which
+    ///   catches exactly that: files edited in place with the sidecar left
+    ///   untouched.
+    ///
+    /// A missing `.cargo-checksum.json` is a hard error rather than a
+    /// silent skip: without it there is no per-file digest to re-hash
+    /// against, and the original `.crate` tarball (the thing `expected`
+    /// actually hashes) is not present in the vendor tree to re-derive it
+    /// from either.
+    fn verify_vendored(&self, crate_dir: &Path, expected: &str) -> Result<()> {
+        let checksum_path = crate_dir.join(".cargo-checksum.json");
+        let content = fs::read(&checksum_path)
+            .with_context(|| format!("Failed to read {}", checksum_path.display()))?;
+        let parsed: CargoChecksumFile = serde_json::from_slice(&content)
+            .with_context(|| format!("Failed to parse {}", checksum_path.display()))?;
+
+        if let Some(package) = &parsed.package {
+            if package != expected {
+                bail!(
+                    "Checksum mismatch for vendored package {} {}: Cargo.lock says {}, \
+                     but {} says {}",
+                    self.name,
+                    self.version,
+                    expected,
+                    checksum_path.display(),
+                    package,
+                );
+            }
+        }
+
+        for (relative, expected_file_digest) in &parsed.files {
+            let file = crate_dir.join(relative);
+            let content =
+                fs::read(&file).with_context(|| format!("Failed to read {}", file.display()))?;
+            let actual_file_digest = format!("{:x}", Sha256::digest(&content));
+            if &actual_file_digest != expected_file_digest {
+                bail!(
+                    "Checksum mismatch for {} in vendored package {} {}: \
+                     {} says {}, but the file hashes to {}",
+                    relative,
+                    self.name,
+                    self.version,
+                    checksum_path.display(),
+                    expected_file_digest,
+                    actual_file_digest,
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The subset of Cargo's `.cargo-checksum.json` vendoring sidecar that we
+/// care about: the checksum of the package as a whole, and a per-file digest
+/// map, both used by `cargo vendor` (and Reindeer's own vendoring) to detect
+/// local tampering.
+#[derive(Deserialize, Debug)]
+#[cfg_attr(test, derive(serde::Serialize))]
+struct CargoChecksumFile {
+    package: Option<String>,
+    #[serde(default)]
+    files: std::collections::BTreeMap<String, String>,
+}
+
+/// Bare-bones view of a lockfile used only to recover the `[metadata]` table
+/// that versions 1 and 2 use to store package checksums out-of-line.
+#[derive(Deserialize, Debug)]
+struct RawLockfileMetadata {
+    #[serde(default)]
+    metadata: std::collections::BTreeMap<String, String>,
+}
+
+impl<'de> Deserialize<'de> for LockfileVersion {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: Deserializer<'de>,
     {
         let version = usize::deserialize(deserializer)?;
-        if version != 3 {
-            log::warn!("Unrecognized Cargo.lock format version: {}", version);
+        match version {
+            1 => Ok(LockfileVersion::V1),
+            2 => Ok(LockfileVersion::V2),
+            3 => Ok(LockfileVersion::V3),
+            4 => Ok(LockfileVersion::V4),
+            other => Err(serde::de::Error::custom(format!(
+                "unrecognized Cargo.lock format version: {}",
+                other
+            ))),
         }
-        Ok(Hopefully3)
+    }
+}
+
+impl fmt::Display for LockfileVersion {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}", self.as_usize())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    #[test]
+    fn parses_v1_lockfile_with_no_version_key() {
+        let lockfile = Lockfile::parse(
+            br#"
+[[package]]
+name = "libc"
+version = "0.2.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(lockfile.version, Some(LockfileVersion::V1));
+        assert_eq!(lockfile.packages.len(), 1);
+        assert_eq!(lockfile.packages[0].checksum, None);
+    }
+
+    #[test]
+    fn parses_v2_lockfile_and_backfills_checksum_from_metadata() {
+        let lockfile = Lockfile::parse(
+            br#"
+[[package]]
+name = "libc"
+version = "0.2.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[metadata]
+"checksum libc 0.2.0 (registry+https://github.com/rust-lang/crates.io-index)" = "deadbeef"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(lockfile.version, Some(LockfileVersion::V2));
+        assert_eq!(lockfile.packages[0].checksum, Some("deadbeef".to_owned()));
+    }
+
+    #[test]
+    fn parses_v3_lockfile_with_inline_checksum() {
+        let lockfile = Lockfile::parse(
+            br#"
+version = 3
+
+[[package]]
+name = "libc"
+version = "0.2.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "deadbeef"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(lockfile.version, Some(LockfileVersion::V3));
+        assert_eq!(lockfile.packages[0].checksum, Some("deadbeef".to_owned()));
+    }
+
+    fn unique_test_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "reindeer-lockfile-test-{}-{}-{:?}",
+            name,
+            std::process::id(),
+            std::time::SystemTime::now()
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn verify_vendored_detects_package_checksum_mismatch() {
+        let crate_dir = unique_test_dir("package-mismatch");
+        fs::write(
+            crate_dir.join(".cargo-checksum.json"),
+            r#"{"package": "aaaa", "files": {}}"#,
+        )
+        .unwrap();
+
+        let pkg = LockfilePackage {
+            name: "libc".to_owned(),
+            version: "0.2.0".parse().unwrap(),
+            source: None,
+            checksum: Some("bbbb".to_owned()),
+        };
+        let err = pkg.verify_vendored(&crate_dir, "bbbb").unwrap_err();
+        assert!(err.to_string().contains("Checksum mismatch"));
+    }
+
+    #[test]
+    fn verify_vendored_detects_tampered_file() {
+        let crate_dir = unique_test_dir("tampered-file");
+        fs::write(crate_dir.join("lib.rs"), b"original contents").unwrap();
+        let digest = format!("{:x}", Sha256::digest(b"original contents"));
+        let mut files = BTreeMap::new();
+        files.insert("lib.rs".to_owned(), digest);
+        let checksum_file = CargoChecksumFile {
+            package: None,
+            files,
+        };
+        fs::write(
+            crate_dir.join(".cargo-checksum.json"),
+            serde_json::to_vec(&checksum_file).unwrap(),
+        )
+        .unwrap();
+
+        // Tamper with the file after the sidecar was written.
+        fs::write(crate_dir.join("lib.rs"), b"tampered contents").unwrap();
+
+        let pkg = LockfilePackage {
+            name: "libc".to_owned(),
+            version: "0.2.0".parse().unwrap(),
+            source: None,
+            checksum: Some("whatever".to_owned()),
+        };
+        let err = pkg.verify_vendored(&crate_dir, "whatever").unwrap_err();
+        assert!(err.to_string().contains("lib.rs"));
+    }
+
+    #[test]
+    fn verify_vendored_passes_for_untampered_tree() {
+        let crate_dir = unique_test_dir("untampered");
+        fs::write(crate_dir.join("lib.rs"), b"original contents").unwrap();
+        let digest = format!("{:x}", Sha256::digest(b"original contents"));
+        let mut files = BTreeMap::new();
+        files.insert("lib.rs".to_owned(), digest);
+        let checksum_file = CargoChecksumFile {
+            package: Some("whatever".to_owned()),
+            files,
+        };
+        fs::write(
+            crate_dir.join(".cargo-checksum.json"),
+            serde_json::to_vec(&checksum_file).unwrap(),
+        )
+        .unwrap();
+
+        let pkg = LockfilePackage {
+            name: "libc".to_owned(),
+            version: "0.2.0".parse().unwrap(),
+            source: None,
+            checksum: Some("whatever".to_owned()),
+        };
+        pkg.verify_vendored(&crate_dir, "whatever").unwrap();
     }
 }